@@ -1,31 +1,142 @@
 use chrono::prelude::*;
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
 
-use tags::{Identifiable, TagSet, TagSetID};
+use tags::{Expr, Identifiable, TagSet, TagSetID};
+use value::Value;
 
 pub struct DB {
     config: Config,
-    hot_slabs: HashMap<TagSetID, Vec<Slab>>,
+    hot_slabs: HashMap<TagSetID, HotSeries>,
 }
 
 pub struct Config {
     slab_duration: i64,
+    data_dir: PathBuf,
+    flush_age_threshold: i64,
+}
+
+// Everything the DB keeps in memory for one series: the tags it was written
+// with (so `read_where` can match it without re-parsing a `TagSetID`), the
+// value type its first write established, and its hot slabs.
+struct HotSeries {
+    tag_set: TagSet,
+    value_type: Option<u8>,
+    slabs: Vec<Slab>,
 }
 
 struct Slab {
     start_time: i64,
     duration: i64,
     times: Vec<i64>,
-    values: Vec<f64>,
+    values: Vec<Value>,
     last_modified_time: i64,
 }
 
-type Series = (Vec<i64>, Vec<f64>);
+type Series = (Vec<i64>, Vec<Value>);
+
+/// Returned by [`DB::write`] when a series already holds a different
+/// [`Value`] variant than the one being written.
+#[derive(Debug, PartialEq)]
+pub struct TypeMismatchError {
+    expected: &'static str,
+    found: &'static str,
+}
+
+impl fmt::Display for TypeMismatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot write a {} value into a series that already holds {} values",
+            self.found, self.expected
+        )
+    }
+}
+
+impl std::error::Error for TypeMismatchError {}
+
+/// How to fold the points in a time bucket down to a single [`Value`] in
+/// [`DB::read_aggregated`].
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Aggregator {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Mean,
+    First,
+    Last,
+}
+
+// Running accumulator for one time bucket, folded point-by-point as
+// `read_aggregated` streams over the time-sorted series. `sum`/`min`/`max`
+// are only meaningful (and only touched) for the aggregators that need a
+// numeric value, so `Count`/`First`/`Last` work over non-numeric `Value`
+// variants like `Text`/`Binary` too.
+struct BucketAcc {
+    start: i64,
+    agg: Aggregator,
+    count: u64,
+    sum: f64,
+    min: Value,
+    max: Value,
+    first: Value,
+    last: Value,
+}
+
+impl BucketAcc {
+    fn new(start: i64, value: Value, agg: Aggregator) -> BucketAcc {
+        let sum = match agg {
+            Aggregator::Sum | Aggregator::Mean => value.as_f64(),
+            _ => 0.0,
+        };
+
+        BucketAcc {
+            start,
+            agg,
+            count: 1,
+            sum,
+            min: value.clone(),
+            max: value.clone(),
+            first: value.clone(),
+            last: value,
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        self.count += 1;
+
+        match self.agg {
+            Aggregator::Sum | Aggregator::Mean => self.sum += value.as_f64(),
+            Aggregator::Min if value.as_f64() < self.min.as_f64() => self.min = value.clone(),
+            Aggregator::Max if value.as_f64() > self.max.as_f64() => self.max = value.clone(),
+            _ => {}
+        }
+
+        self.last = value;
+    }
+
+    fn finish(self, agg: Aggregator) -> Value {
+        match agg {
+            Aggregator::Count => Value::U64(self.count),
+            Aggregator::Sum => Value::F64(self.sum),
+            Aggregator::Mean => Value::F64(self.sum / self.count as f64),
+            Aggregator::Min => self.min,
+            Aggregator::Max => self.max,
+            Aggregator::First => self.first,
+            Aggregator::Last => self.last,
+        }
+    }
+}
 
 impl Slab {
     fn new(start_time: i64, duration: i64) -> Slab {
         let times: Vec<i64> = Vec::new();
-        let values: Vec<f64> = Vec::new();
+        let values: Vec<Value> = Vec::new();
         let last_modified_time = Utc::now().timestamp_nanos();
 
         return Slab {
@@ -37,30 +148,159 @@ impl Slab {
         };
     }
 
-    fn write(&mut self, time: i64, value: f64) {
+    fn write(&mut self, time: i64, value: Value) {
         self.times.push(time);
         self.values.push(value);
         self.last_modified_time = Utc::now().timestamp_nanos();
     }
 }
 
+// A cold segment is a sequence of `(time: i64, value: Value)` records,
+// little-endian and sorted by time. One segment file per `TagSetID`.
+
+// A `TagSetID` is built straight from user-supplied tag keys/values
+// (`"key=value,..."`), so it can't be trusted as a filesystem path
+// component as-is -- a tag containing `../` would escape `data_dir`.
+// Percent-encodes everything but a safe allowlist (ASCII alphanumerics and
+// `-`, `_`, `=`, `,`), so the encoded form never contains a `.` or `/` to
+// traverse with.
+fn encode_path_component(id: &str) -> String {
+    let mut out = String::with_capacity(id.len());
+
+    for b in id.bytes() {
+        match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'=' | b',' => {
+                out.push(b as char)
+            }
+            _ => out.push_str(&format!("%{:02x}", b)),
+        }
+    }
+
+    out
+}
+
+fn write_point(writer: &mut impl Write, time: i64, value: &Value) -> io::Result<()> {
+    writer.write_all(&time.to_le_bytes())?;
+    value.write_to(writer)
+}
+
+fn read_point(reader: &mut impl Read) -> io::Result<Option<(i64, Value)>> {
+    let mut time_bytes = [0u8; 8];
+
+    match reader.read_exact(&mut time_bytes) {
+        Ok(()) => {}
+        Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+
+    let time = i64::from_le_bytes(time_bytes);
+    let value = Value::read_from(reader)?.expect("segment ended mid-record");
+
+    Ok(Some((time, value)))
+}
+
+// One run in the k-way merge: the next point it has buffered, plus the
+// reader to pull more from. Ordered so a `BinaryHeap` (a max-heap) pops the
+// run with the *smallest* next timestamp first.
+struct Run {
+    reader: BufReader<File>,
+    next: (i64, Value),
+}
+
+impl PartialEq for Run {
+    fn eq(&self, other: &Self) -> bool {
+        self.next.0 == other.next.0
+    }
+}
+
+impl Eq for Run {}
+
+impl PartialOrd for Run {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Run {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.next.0.cmp(&self.next.0)
+    }
+}
+
+// Merges already-sorted `(time, value)` runs (each a temporary file of
+// `write_point` records) into one sorted segment at `output_path`, via a
+// binary heap keyed on timestamp so the merge never holds more than one
+// buffered point per run in memory.
+fn merge_runs(run_paths: &[PathBuf], output_path: &Path) -> io::Result<()> {
+    let mut heap = BinaryHeap::new();
+
+    for run_path in run_paths {
+        let mut reader = BufReader::new(File::open(run_path)?);
+
+        if let Some(next) = read_point(&mut reader)? {
+            heap.push(Run { reader, next });
+        }
+    }
+
+    let tmp_output_path =
+        output_path.with_file_name(format!("{}.tmp", output_path.file_name().unwrap().to_string_lossy()));
+
+    {
+        let mut writer = BufWriter::new(File::create(&tmp_output_path)?);
+
+        while let Some(mut run) = heap.pop() {
+            write_point(&mut writer, run.next.0, &run.next.1)?;
+
+            if let Some(next) = read_point(&mut run.reader)? {
+                run.next = next;
+                heap.push(run);
+            }
+        }
+
+        writer.flush()?;
+    }
+
+    fs::rename(&tmp_output_path, output_path)?;
+
+    for run_path in run_paths {
+        fs::remove_file(run_path)?;
+    }
+
+    Ok(())
+}
+
 impl DB {
-    pub fn new(config: Config) -> DB {
-        let hot_slabs: HashMap<TagSetID, Vec<Slab>> = HashMap::new();
+    // Rehydrates `hot_slabs` (as empty slab lists) for any series that
+    // already has a cold segment in `config.data_dir`, so `read_where` can
+    // find series from a prior run before anything is written to them again.
+    pub fn new(config: Config) -> io::Result<DB> {
+        let hot_slabs = Self::rehydrate_hot_slabs(&config.data_dir)?;
 
-        return DB { config, hot_slabs };
+        Ok(DB { config, hot_slabs })
     }
 
-    pub fn write(&mut self, tag_set: &TagSet, time: i64, value: f64) {
-        let slabs = self
+    pub fn write(&mut self, tag_set: &TagSet, time: i64, value: Value) -> Result<(), TypeMismatchError> {
+        let entry = self
             .hot_slabs
-            .entry(tag_set.id().to_owned())
-            .or_insert_with(|| {
-                let ss: Vec<Slab> = Vec::new();
-
-                ss
+            .entry(tag_set.id())
+            .or_insert_with(|| HotSeries {
+                tag_set: tag_set.clone(),
+                value_type: None,
+                slabs: Vec::new(),
             });
 
+        match entry.value_type {
+            Some(expected) if expected != value.type_tag() => {
+                return Err(TypeMismatchError {
+                    expected: Value::type_name_for_tag(expected),
+                    found: value.type_name(),
+                });
+            }
+            _ => entry.value_type = Some(value.type_tag()),
+        }
+
+        let slabs = &mut entry.slabs;
+
         let maybe_slab = slabs
             .iter_mut()
             .find(|x| x.start_time <= time && x.start_time + x.duration < time);
@@ -76,37 +316,267 @@ impl DB {
         };
 
         slab.write(time, value);
+
+        Ok(())
     }
 
     pub fn read(&self, tag_set: &TagSet, start_time: i64, stop_time: i64) -> Series {
-        let maybe_slabs = self.hot_slabs.get(&tag_set.id());
+        let tag_set_id = tag_set.id();
 
-        if maybe_slabs.is_none() {
-            let times: Vec<i64> = Vec::new();
-            let values: Vec<f64> = Vec::new();
+        let mut points = match self.hot_slabs.get(&tag_set_id) {
+            Some(series) => Self::collect_points(&series.slabs, start_time, stop_time),
+            None => Vec::new(),
+        };
+
+        points.extend(
+            self.read_cold_points(&tag_set_id, start_time, stop_time)
+                .expect("failed to read cold segment"),
+        );
+
+        points.sort_by_key(|p| p.0);
+        points.into_iter().unzip()
+    }
+
+    pub fn read_where(&self, expr: &str, start_time: i64, stop_time: i64) -> Series {
+        let expr = Expr::parse(expr);
+
+        let mut points: Vec<(i64, Value)> = Vec::new();
 
-            return (times, values);
+        for (tag_set_id, series) in self.hot_slabs.iter() {
+            if !series.tag_set.matches_logical(&expr) {
+                continue;
+            }
+
+            points.extend(Self::collect_points(&series.slabs, start_time, stop_time));
+            points.extend(
+                self.read_cold_points(tag_set_id, start_time, stop_time)
+                    .expect("failed to read cold segment"),
+            );
         }
 
-        let mut points: Vec<(i64, f64)> = Vec::new();
+        points.sort_by_key(|p| p.0);
+        points.into_iter().unzip()
+    }
 
-        for slab in maybe_slabs.unwrap().iter() {
+    // Rolls the points in `[start_time, stop_time)` up into fixed `window`-
+    // sized buckets, each folded down to one `Value` by `agg`. Buckets with
+    // no points are omitted rather than zero-filled.
+    pub fn read_aggregated(
+        &self,
+        tag_set: &TagSet,
+        start_time: i64,
+        stop_time: i64,
+        window: i64,
+        agg: Aggregator,
+    ) -> Series {
+        let (times, values) = self.read(tag_set, start_time, stop_time);
+
+        let mut out_times = Vec::new();
+        let mut out_values = Vec::new();
+        let mut bucket: Option<BucketAcc> = None;
+
+        for (time, value) in times.into_iter().zip(values) {
+            let bucket_start = start_time + ((time - start_time) / window) * window;
+
+            bucket = Some(match bucket {
+                Some(mut acc) if acc.start == bucket_start => {
+                    acc.push(value);
+                    acc
+                }
+                Some(acc) => {
+                    out_times.push(acc.start);
+                    out_values.push(acc.finish(agg));
+
+                    BucketAcc::new(bucket_start, value, agg)
+                }
+                None => BucketAcc::new(bucket_start, value, agg),
+            });
+        }
+
+        if let Some(acc) = bucket {
+            out_times.push(acc.start);
+            out_values.push(acc.finish(agg));
+        }
+
+        (out_times, out_values)
+    }
+
+    fn collect_points(slabs: &[Slab], start_time: i64, stop_time: i64) -> Vec<(i64, Value)> {
+        let mut points = Vec::new();
+
+        for slab in slabs.iter() {
             if slab.start_time >= stop_time || slab.start_time + slab.duration <= start_time {
                 continue;
             }
 
             for (i, time) in slab.times.iter().enumerate() {
                 if *time >= start_time && *time < stop_time {
-                    points.push((*time, slab.values[i]))
+                    points.push((*time, slab.values[i].clone()));
                 }
             }
         }
 
-        points.sort_by_key(|p| p.0);
-        points.into_iter().unzip()
+        points
     }
 
-    pub fn flush(&self) {}
+    fn segment_path(data_dir: &Path, tag_set_id: &str) -> PathBuf {
+        data_dir.join(format!("{}.segment", encode_path_component(tag_set_id)))
+    }
+
+    // Sidecar next to a series' segment, holding its value type tag and raw
+    // `TagSetID` (one byte, then the id as utf-8) so `rehydrate_hot_slabs`
+    // can reconstruct the `HotSeries` a cold-only series was written with
+    // after a restart, without peeking into the segment itself.
+    fn tag_set_path(data_dir: &Path, tag_set_id: &str) -> PathBuf {
+        data_dir.join(format!("{}.tagset", encode_path_component(tag_set_id)))
+    }
+
+    fn write_tag_set_meta(path: &Path, tag_set_id: &str, value_type: u8) -> io::Result<()> {
+        let mut contents = Vec::with_capacity(1 + tag_set_id.len());
+
+        contents.push(value_type);
+        contents.extend_from_slice(tag_set_id.as_bytes());
+
+        fs::write(path, contents)
+    }
+
+    fn read_tag_set_meta(path: &Path) -> io::Result<(TagSetID, u8)> {
+        let contents = fs::read(path)?;
+        let value_type = contents[0];
+        let tag_set_id = String::from_utf8(contents[1..].to_vec())
+            .expect("tag set id was not valid utf-8");
+
+        Ok((tag_set_id, value_type))
+    }
+
+    // Rebuilds `hot_slabs` entries (with empty slab lists) for every series
+    // that has a cold segment on disk from a prior run, so `read_where`
+    // (which only matches against `hot_slabs`) still finds series that
+    // exist solely in cold storage after a restart, and `write` still
+    // enforces the series' established value type.
+    fn rehydrate_hot_slabs(data_dir: &Path) -> io::Result<HashMap<TagSetID, HotSeries>> {
+        let mut hot_slabs = HashMap::new();
+
+        let entries = match fs::read_dir(data_dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(hot_slabs),
+            Err(e) => return Err(e),
+        };
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some("tagset") {
+                continue;
+            }
+
+            let (tag_set_id, value_type) = Self::read_tag_set_meta(&path)?;
+            let tag_set = TagSet::from_id(&tag_set_id);
+
+            hot_slabs.insert(
+                tag_set_id,
+                HotSeries {
+                    tag_set,
+                    value_type: Some(value_type),
+                    slabs: Vec::new(),
+                },
+            );
+        }
+
+        Ok(hot_slabs)
+    }
+
+    fn read_cold_points(
+        &self,
+        tag_set_id: &str,
+        start_time: i64,
+        stop_time: i64,
+    ) -> io::Result<Vec<(i64, Value)>> {
+        let path = Self::segment_path(&self.config.data_dir, tag_set_id);
+
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut points = Vec::new();
+
+        while let Some((time, value)) = read_point(&mut reader)? {
+            if time >= start_time && time < stop_time {
+                points.push((time, value));
+            }
+        }
+
+        Ok(points)
+    }
+
+    // Serializes slabs idle for at least `config.flush_age_threshold` to
+    // on-disk segments and drops them from `hot_slabs`. Each flushed slab's
+    // points are written to their own temporary run, then all runs (plus any
+    // existing cold segment) are k-way merged into a single time-ordered
+    // segment, so cold reads never need to sort more than they stream.
+    pub fn flush(&mut self) -> io::Result<()> {
+        fs::create_dir_all(&self.config.data_dir)?;
+
+        let now = Utc::now().timestamp_nanos();
+        let threshold = self.config.flush_age_threshold;
+        let data_dir = &self.config.data_dir;
+
+        for (tag_set_id, series) in self.hot_slabs.iter_mut() {
+            let (stale, fresh): (Vec<Slab>, Vec<Slab>) = series
+                .slabs
+                .drain(..)
+                .partition(|slab| now - slab.last_modified_time >= threshold);
+
+            series.slabs = fresh;
+
+            if stale.is_empty() {
+                continue;
+            }
+
+            let value_type = series
+                .value_type
+                .expect("a series with slabs to flush must have a recorded value_type");
+
+            Self::flush_slabs(data_dir, tag_set_id, value_type, stale)?;
+        }
+
+        Ok(())
+    }
+
+    fn flush_slabs(data_dir: &Path, tag_set_id: &str, value_type: u8, stale: Vec<Slab>) -> io::Result<()> {
+        Self::write_tag_set_meta(&Self::tag_set_path(data_dir, tag_set_id), tag_set_id, value_type)?;
+
+        let segment_path = Self::segment_path(data_dir, tag_set_id);
+        let encoded_id = encode_path_component(tag_set_id);
+        let mut run_paths = Vec::new();
+
+        if segment_path.exists() {
+            let existing_run = data_dir.join(format!("{}.existing.tmp", encoded_id));
+
+            fs::rename(&segment_path, &existing_run)?;
+            run_paths.push(existing_run);
+        }
+
+        for (i, slab) in stale.into_iter().enumerate() {
+            let mut points: Vec<(i64, Value)> =
+                slab.times.into_iter().zip(slab.values).collect();
+
+            points.sort_by_key(|p| p.0);
+
+            let run_path = data_dir.join(format!("{}.run{}.tmp", encoded_id, i));
+            let mut writer = BufWriter::new(File::create(&run_path)?);
+
+            for (time, value) in &points {
+                write_point(&mut writer, *time, value)?;
+            }
+
+            writer.flush()?;
+            run_paths.push(run_path);
+        }
+
+        merge_runs(&run_paths, &segment_path)
+    }
 }
 
 #[cfg(test)]
@@ -114,9 +584,17 @@ mod test {
     use super::*;
     use tags::parse_tag_set;
 
+    fn test_config(slab_duration: i64) -> Config {
+        Config {
+            slab_duration,
+            data_dir: PathBuf::from("/nonexistent-tsd-test-data-dir"),
+            flush_age_threshold: i64::MAX,
+        }
+    }
+
     #[test]
     fn write_then_read_series() {
-        let mut db = DB::new(Config { slab_duration: 10 });
+        let mut db = DB::new(test_config(10)).unwrap();
 
         let tag_set_a = parse_tag_set(r#""a" = "A", "b" = "B""#);
         let tag_set_b = parse_tag_set(r#""b" = "B", "c" = "C""#);
@@ -126,34 +604,46 @@ mod test {
         //     [5,   7,   8,   20,  22]
         //     [1.0, 8.1, 2.4, 3.0, 120.6]
         //
-        db.write(&tag_set_a, 5, 1.0);
-        db.write(&tag_set_a, 7, 8.1);
-        db.write(&tag_set_a, 8, 2.4);
-        db.write(&tag_set_a, 20, 3.0);
-        db.write(&tag_set_a, 22, 120.6);
+        db.write(&tag_set_a, 5, Value::F64(1.0)).unwrap();
+        db.write(&tag_set_a, 7, Value::F64(8.1)).unwrap();
+        db.write(&tag_set_a, 8, Value::F64(2.4)).unwrap();
+        db.write(&tag_set_a, 20, Value::F64(3.0)).unwrap();
+        db.write(&tag_set_a, 22, Value::F64(120.6)).unwrap();
 
         // for `tag_set_b`, write the series
         //
         //     [7,    20]
         //     [2.2, -1.1]
         //
-        db.write(&tag_set_b, 7, 2.2);
-        db.write(&tag_set_b, 20, -1.1);
+        db.write(&tag_set_b, 7, Value::F64(2.2)).unwrap();
+        db.write(&tag_set_b, 20, Value::F64(-1.1)).unwrap();
 
         let (actual_times0, actual_values0) = db.read(&tag_set_a, 6, 22);
 
         assert_eq!(actual_times0, vec![7, 8, 20]);
-        assert_eq!(actual_values0, vec![8.1, 2.4, 3.0]);
+        assert_eq!(
+            actual_values0,
+            vec![Value::F64(8.1), Value::F64(2.4), Value::F64(3.0)]
+        );
 
         let (actual_times1, actual_values1) = db.read(&tag_set_a, 0, 50);
 
         assert_eq!(actual_times1, vec![5, 7, 8, 20, 22]);
-        assert_eq!(actual_values1, vec![1.0, 8.1, 2.4, 3.0, 120.6]);
+        assert_eq!(
+            actual_values1,
+            vec![
+                Value::F64(1.0),
+                Value::F64(8.1),
+                Value::F64(2.4),
+                Value::F64(3.0),
+                Value::F64(120.6),
+            ]
+        );
 
         let (actual_times2, actual_values2) = db.read(&tag_set_b, 0, 50);
 
         assert_eq!(actual_times2, vec![7, 20]);
-        assert_eq!(actual_values2, vec![2.2, -1.1]);
+        assert_eq!(actual_values2, vec![Value::F64(2.2), Value::F64(-1.1)]);
 
         let (actual_times3, actual_values3) = db.read(&tag_set_b, 50, 100);
 
@@ -161,11 +651,275 @@ mod test {
         assert_eq!(actual_values3, vec![]);
     }
 
-    #[ignore]
     #[test]
     fn write_then_read_series_partial_tag_set() {
+        let mut db = DB::new(test_config(10)).unwrap();
+
+        let tag_set_a = parse_tag_set(r#""a" = "A", "b" = "B""#);
+        let tag_set_b = parse_tag_set(r#""b" = "B", "c" = "C""#);
+
         // write series "a=A,b=B" and "b=B,c=C"
-        // read series b=B
-        // should return all results from both series
+        db.write(&tag_set_a, 5, Value::F64(1.0)).unwrap();
+        db.write(&tag_set_a, 7, Value::F64(8.1)).unwrap();
+        db.write(&tag_set_b, 6, Value::F64(2.2)).unwrap();
+        db.write(&tag_set_b, 20, Value::F64(-1.1)).unwrap();
+
+        // read series b=B, which should return all results from both series
+        let (actual_times, actual_values) = db.read_where(r#""b" == "B""#, 0, 50);
+
+        assert_eq!(actual_times, vec![5, 6, 7, 20]);
+        assert_eq!(
+            actual_values,
+            vec![
+                Value::F64(1.0),
+                Value::F64(2.2),
+                Value::F64(8.1),
+                Value::F64(-1.1),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_where_with_and_or_expr() {
+        let mut db = DB::new(test_config(10)).unwrap();
+
+        let tag_set_a = parse_tag_set(r#""host" = "a", "region" = "us-west""#);
+        let tag_set_b = parse_tag_set(r#""host" = "b", "region" = "us-west""#);
+        let tag_set_c = parse_tag_set(r#""host" = "c", "region" = "us-east""#);
+
+        db.write(&tag_set_a, 1, Value::F64(1.0)).unwrap();
+        db.write(&tag_set_b, 2, Value::F64(2.0)).unwrap();
+        db.write(&tag_set_c, 3, Value::F64(3.0)).unwrap();
+
+        let (actual_times, actual_values) =
+            db.read_where(r#""host" == "a" or "region" == "us-east""#, 0, 10);
+
+        assert_eq!(actual_times, vec![1, 3]);
+        assert_eq!(actual_values, vec![Value::F64(1.0), Value::F64(3.0)]);
+    }
+
+    #[test]
+    fn write_rejects_a_value_of_a_different_type_than_the_series() {
+        let mut db = DB::new(test_config(10)).unwrap();
+        let tag_set = parse_tag_set(r#""host" = "a""#);
+
+        db.write(&tag_set, 1, Value::F64(1.0)).unwrap();
+
+        let err = db.write(&tag_set, 2, Value::U64(2)).unwrap_err();
+
+        assert_eq!(
+            err,
+            TypeMismatchError {
+                expected: "f64",
+                found: "u64",
+            }
+        );
+    }
+
+    #[test]
+    fn read_aggregated_buckets_and_folds_points() {
+        let mut db = DB::new(test_config(10)).unwrap();
+        let tag_set = parse_tag_set(r#""host" = "a""#);
+
+        // bucket [0, 10): 1.0, 2.0, 3.0
+        db.write(&tag_set, 1, Value::F64(1.0)).unwrap();
+        db.write(&tag_set, 5, Value::F64(2.0)).unwrap();
+        db.write(&tag_set, 9, Value::F64(3.0)).unwrap();
+
+        // bucket [10, 20): empty, omitted
+
+        // bucket [20, 30): 10.0
+        db.write(&tag_set, 25, Value::F64(10.0)).unwrap();
+
+        let (times, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Sum);
+        assert_eq!(times, vec![0, 20]);
+        assert_eq!(values, vec![Value::F64(6.0), Value::F64(10.0)]);
+
+        let (times, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Count);
+        assert_eq!(times, vec![0, 20]);
+        assert_eq!(values, vec![Value::U64(3), Value::U64(1)]);
+
+        let (times, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Mean);
+        assert_eq!(times, vec![0, 20]);
+        assert_eq!(values, vec![Value::F64(2.0), Value::F64(10.0)]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Min);
+        assert_eq!(values, vec![Value::F64(1.0), Value::F64(10.0)]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Max);
+        assert_eq!(values, vec![Value::F64(3.0), Value::F64(10.0)]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::First);
+        assert_eq!(values, vec![Value::F64(1.0), Value::F64(10.0)]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 30, 10, Aggregator::Last);
+        assert_eq!(values, vec![Value::F64(3.0), Value::F64(10.0)]);
+    }
+
+    #[test]
+    fn read_aggregated_count_first_last_work_on_non_numeric_values() {
+        let mut db = DB::new(test_config(10)).unwrap();
+        let tag_set = parse_tag_set(r#""host" = "a""#);
+
+        db.write(&tag_set, 1, Value::Text("a".to_string())).unwrap();
+        db.write(&tag_set, 5, Value::Text("b".to_string())).unwrap();
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 10, 10, Aggregator::Count);
+        assert_eq!(values, vec![Value::U64(2)]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 10, 10, Aggregator::First);
+        assert_eq!(values, vec![Value::Text("a".to_string())]);
+
+        let (_, values) = db.read_aggregated(&tag_set, 0, 10, 10, Aggregator::Last);
+        assert_eq!(values, vec![Value::Text("b".to_string())]);
+    }
+
+    #[test]
+    fn flush_moves_stale_slabs_to_cold_storage_and_merges_on_read() {
+        let data_dir = std::env::temp_dir().join("tsd-test-flush-moves-stale-slabs");
+        let _ = fs::remove_dir_all(&data_dir);
+
+        let mut db = DB::new(Config {
+            slab_duration: 10,
+            data_dir: data_dir.clone(),
+            flush_age_threshold: 0, // flush everything immediately
+        })
+        .unwrap();
+
+        let tag_set_a = parse_tag_set(r#""a" = "A""#);
+
+        db.write(&tag_set_a, 5, Value::F64(1.0)).unwrap();
+        db.write(&tag_set_a, 20, Value::F64(3.0)).unwrap();
+
+        db.flush().unwrap();
+
+        assert!(data_dir.join(format!("{}.segment", tag_set_a.id())).exists());
+
+        // more writes land back in hot storage alongside the cold segment
+        db.write(&tag_set_a, 7, Value::F64(8.1)).unwrap();
+
+        let (actual_times, actual_values) = db.read(&tag_set_a, 0, 30);
+
+        assert_eq!(actual_times, vec![5, 7, 20]);
+        assert_eq!(
+            actual_values,
+            vec![Value::F64(1.0), Value::F64(8.1), Value::F64(3.0)]
+        );
+
+        // flushing again should merge the new slab into the existing segment
+        db.flush().unwrap();
+
+        let (actual_times, actual_values) = db.read(&tag_set_a, 0, 30);
+
+        assert_eq!(actual_times, vec![5, 7, 20]);
+        assert_eq!(
+            actual_values,
+            vec![Value::F64(1.0), Value::F64(8.1), Value::F64(3.0)]
+        );
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[test]
+    fn flush_does_not_escape_data_dir_via_tag_values() {
+        let base = std::env::temp_dir().join("tsd-test-flush-path-traversal");
+        let _ = fs::remove_dir_all(&base);
+
+        let data_dir = base.join("data");
+        let escape_target = base.join("..").join("escaped=x.segment");
+
+        let mut db = DB::new(Config {
+            slab_duration: 10,
+            data_dir: data_dir.clone(),
+            flush_age_threshold: 0,
+        })
+        .unwrap();
+
+        let tag_set = parse_tag_set(r#""../../escaped" = "x""#);
+
+        db.write(&tag_set, 1, Value::F64(1.0)).unwrap();
+        db.flush().unwrap();
+
+        assert!(!escape_target.exists());
+        assert!(!data_dir.join("..").join("..").join("escaped=x.tagset").exists());
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn read_where_finds_cold_only_series_after_restart() {
+        let data_dir = std::env::temp_dir().join("tsd-test-read-where-after-restart");
+        let _ = fs::remove_dir_all(&data_dir);
+
+        let tag_set = parse_tag_set(r#""host" = "a""#);
+
+        {
+            let mut db = DB::new(Config {
+                slab_duration: 10,
+                data_dir: data_dir.clone(),
+                flush_age_threshold: 0,
+            })
+            .unwrap();
+
+            db.write(&tag_set, 1, Value::F64(1.0)).unwrap();
+            db.flush().unwrap();
+        }
+
+        // a fresh `DB` (as after a process restart) should still find the
+        // series via `read_where`, not just `read`
+        let db = DB::new(Config {
+            slab_duration: 10,
+            data_dir: data_dir.clone(),
+            flush_age_threshold: 0,
+        })
+        .unwrap();
+
+        let (times, values) = db.read_where(r#""host" == "a""#, 0, 10);
+
+        assert_eq!(times, vec![1]);
+        assert_eq!(values, vec![Value::F64(1.0)]);
+
+        fs::remove_dir_all(&data_dir).unwrap();
+    }
+
+    #[test]
+    fn write_rejects_mismatched_type_after_restart() {
+        let data_dir = std::env::temp_dir().join("tsd-test-write-rejects-mismatch-after-restart");
+        let _ = fs::remove_dir_all(&data_dir);
+
+        let tag_set = parse_tag_set(r#""host" = "a""#);
+
+        {
+            let mut db = DB::new(Config {
+                slab_duration: 10,
+                data_dir: data_dir.clone(),
+                flush_age_threshold: 0,
+            })
+            .unwrap();
+
+            db.write(&tag_set, 1, Value::F64(1.0)).unwrap();
+            db.flush().unwrap();
+        }
+
+        // a fresh `DB` over the same data_dir should still know this series
+        // holds f64 values, and reject a write of a different type
+        let mut db = DB::new(Config {
+            slab_duration: 10,
+            data_dir: data_dir.clone(),
+            flush_age_threshold: 0,
+        })
+        .unwrap();
+
+        let err = db.write(&tag_set, 2, Value::U64(2)).unwrap_err();
+
+        assert_eq!(
+            err,
+            TypeMismatchError {
+                expected: "f64",
+                found: "u64",
+            }
+        );
+
+        fs::remove_dir_all(&data_dir).unwrap();
     }
 }
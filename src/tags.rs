@@ -2,15 +2,17 @@
 //
 // - [x] Parse tag sets, e.g. `"abc" = "ced", "h" = "j"`
 // - [x] Parse tag expressions, e.g. `"abc" == "ced" or "h" == "k"`
-// - [ ] Parse tag expressions with parens
+// - [x] Parse tag expressions with parens
 // - [ ] Ability to write by string tag set
-// - [ ] Ability to read based on tag expression
+// - [x] Ability to read based on tag expression
 
 #![allow(dead_code)] // delete me
 
+use pest::iterators::{Pair, Pairs};
 use pest::Parser;
 
 use std::collections::HashMap;
+use std::iter::Peekable;
 
 #[derive(PartialEq, Debug)]
 enum EqualityOp {
@@ -19,7 +21,7 @@ enum EqualityOp {
 }
 
 #[derive(PartialEq, Debug)]
-struct Equality {
+pub(crate) struct Equality {
     op: EqualityOp,
     lhs: String,
     rhs: String,
@@ -31,59 +33,108 @@ enum LogicalOp {
     Or,
 }
 
+impl LogicalOp {
+    // `and` binds tighter than `or`, so it gets a higher precedence level.
+    fn precedence(&self) -> u8 {
+        match self {
+            LogicalOp::Or => 1,
+            LogicalOp::And => 2,
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
-enum Logical {
-    Just(Equality),
-    Also(Equality, LogicalOp, Box<Logical>),
+pub(crate) enum Expr {
+    Eq(Equality),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
 }
 
-impl Logical {
-    fn parse(input: &str) -> Logical {
-        let mut rules = TagsParser::parse(Rule::Logical, input)
+impl Expr {
+    pub(crate) fn parse(input: &str) -> Expr {
+        let logical = TagsParser::parse(Rule::Logical, input)
             .unwrap() // fixme
             .next()
-            .unwrap()
-            .into_inner();
+            .unwrap();
 
-        let mut equality_rule = rules.next().unwrap().into_inner();
+        Self::parse_logical(logical)
+    }
 
-        let lhs = equality_rule
-            .next()
-            .unwrap()
-            .as_str()
-            .trim_matches('"')
-            .to_string();
+    // Parses a `Logical` pair (`primary ((and | or) primary)*`) via precedence
+    // climbing: take the leading primary as `lhs`, then repeatedly fold in an
+    // operator and its right-hand side as long as the operator binds at least
+    // as tightly as `min_precedence`, recursing to a higher minimum to let a
+    // tighter-binding operator steal the rhs first.
+    fn parse_logical(logical: Pair<Rule>) -> Expr {
+        let mut pairs = logical.into_inner().peekable();
+        let lhs = Self::parse_primary(pairs.next().unwrap());
 
-        let op = match equality_rule.next().unwrap().as_rule() {
-            Rule::Equals => EqualityOp::Equals,
-            Rule::NotEquals => EqualityOp::NotEquals,
-            _ => unreachable!(),
-        };
+        Self::parse_expr(lhs, &mut pairs, 0)
+    }
 
-        let rhs = equality_rule
-            .next()
-            .unwrap()
-            .as_str()
-            .trim_matches('"')
-            .to_string();
+    fn parse_expr(mut lhs: Expr, pairs: &mut Peekable<Pairs<Rule>>, min_precedence: u8) -> Expr {
+        loop {
+            let op = match pairs.peek().map(|p| p.as_rule()) {
+                Some(Rule::And) => LogicalOp::And,
+                Some(Rule::Or) => LogicalOp::Or,
+                _ => break,
+            };
+
+            if op.precedence() < min_precedence {
+                break;
+            }
+
+            pairs.next(); // consume the operator
 
-        let equality = Equality { op, lhs, rhs };
+            let mut rhs = Self::parse_primary(pairs.next().unwrap());
 
-        let maybe_op = rules.next();
+            loop {
+                let next_precedence = match pairs.peek().map(|p| p.as_rule()) {
+                    Some(Rule::And) => LogicalOp::And.precedence(),
+                    Some(Rule::Or) => LogicalOp::Or.precedence(),
+                    _ => break,
+                };
 
-        if maybe_op.is_none() {
-            return Logical::Just(equality);
+                if next_precedence <= op.precedence() {
+                    break;
+                }
+
+                rhs = Self::parse_expr(rhs, pairs, op.precedence() + 1);
+            }
+
+            lhs = match op {
+                LogicalOp::And => Expr::And(Box::new(lhs), Box::new(rhs)),
+                LogicalOp::Or => Expr::Or(Box::new(lhs), Box::new(rhs)),
+            };
         }
 
-        let op = match maybe_op.unwrap().as_rule() {
-            Rule::And => LogicalOp::And,
-            Rule::Or => LogicalOp::Or,
+        lhs
+    }
+
+    fn parse_primary(primary: Pair<Rule>) -> Expr {
+        let inner = primary.into_inner().next().unwrap();
+
+        match inner.as_rule() {
+            Rule::Equality => Expr::Eq(Self::parse_equality(inner)),
+            Rule::Logical => Self::parse_logical(inner),
+            _ => unreachable!(),
+        }
+    }
+
+    fn parse_equality(equality: Pair<Rule>) -> Equality {
+        let mut rule = equality.into_inner();
+
+        let lhs = rule.next().unwrap().as_str().trim_matches('"').to_string();
+
+        let op = match rule.next().unwrap().as_rule() {
+            Rule::Equals => EqualityOp::Equals,
+            Rule::NotEquals => EqualityOp::NotEquals,
             _ => unreachable!(),
         };
 
-        let next = rules.next().unwrap().as_str();
+        let rhs = rule.next().unwrap().as_str().trim_matches('"').to_string();
 
-        return Logical::Also(equality, op, Box::new(Logical::parse(next)));
+        Equality { op, lhs, rhs }
     }
 }
 
@@ -91,12 +142,19 @@ impl Logical {
 #[grammar = "tags.pest"]
 pub struct TagsParser;
 
+pub type TagSetID = String;
+
+pub trait Identifiable {
+    fn id(&self) -> TagSetID;
+}
+
+#[derive(Clone)]
 pub struct TagSet {
     tags: HashMap<String, String>,
 }
 
-impl TagSet {
-    pub fn id(&self) -> String {
+impl Identifiable for TagSet {
+    fn id(&self) -> TagSetID {
         let mut tags: Vec<String> = self
             .tags
             .iter()
@@ -107,6 +165,32 @@ impl TagSet {
 
         tags.join(",")
     }
+}
+
+pub fn parse_tag_set(input: &str) -> TagSet {
+    TagSet::parse(input)
+}
+
+impl TagSet {
+    // Inverse of `Identifiable::id`: turns a `"k=v,k2=v2"` tag-set id back
+    // into a `TagSet`, so cold-storage metadata can rehydrate the tag set a
+    // series was written with across restarts. Like `id`, this doesn't
+    // escape `=`/`,` in keys or values, so it's only meant to round-trip ids
+    // that `id` itself produced.
+    pub(crate) fn from_id(id: &str) -> TagSet {
+        let mut tags = HashMap::new();
+
+        if !id.is_empty() {
+            for assignment in id.split(',') {
+                if let Some(eq_idx) = assignment.find('=') {
+                    let (key, value) = assignment.split_at(eq_idx);
+                    tags.insert(key.to_string(), value[1..].to_string());
+                }
+            }
+        }
+
+        TagSet { tags }
+    }
 
     pub fn parse(input: &str) -> TagSet {
         let rule = TagsParser::parse(Rule::TagSet, input)
@@ -144,20 +228,19 @@ impl TagSet {
         self.tags.get(key)
     }
 
-    fn matches_logical(&self, logical: Logical) -> bool {
-        match logical {
-            Logical::Just(eq) => self.matches_eq(eq),
-            Logical::Also(eq, LogicalOp::And, tail) => {
-                self.matches_eq(eq) && self.matches_logical(*tail)
-            }
-            Logical::Also(eq, LogicalOp::Or, tail) => {
-                self.matches_eq(eq) || self.matches_logical(*tail)
-            }
+    pub(crate) fn matches_logical(&self, expr: &Expr) -> bool {
+        match expr {
+            Expr::Eq(eq) => self.matches_eq(eq),
+            Expr::And(lhs, rhs) => self.matches_logical(lhs) && self.matches_logical(rhs),
+            Expr::Or(lhs, rhs) => self.matches_logical(lhs) || self.matches_logical(rhs),
         }
     }
 
-    fn matches_eq(&self, equality: Equality) -> bool {
-        let is_equal = self.get(&equality.lhs) == self.get(&equality.rhs);
+    fn matches_eq(&self, equality: &Equality) -> bool {
+        let is_equal = match self.get(&equality.lhs) {
+            Some(value) => value == &equality.rhs,
+            None => false,
+        };
 
         match equality.op {
             EqualityOp::Equals => is_equal,
@@ -179,6 +262,16 @@ mod test {
         assert_eq!(tag_set_b.id(), "a=A,b=B");
     }
 
+    #[test]
+    fn from_id_round_trips_with_id() {
+        let tag_set = TagSet::parse(r#""a" = "A", "b" = "B""#);
+        let rehydrated = TagSet::from_id(&tag_set.id());
+
+        assert_eq!(rehydrated.id(), tag_set.id());
+        assert_eq!(rehydrated.get("a").unwrap(), "A");
+        assert_eq!(rehydrated.get("b").unwrap(), "B");
+    }
+
     #[test]
     fn parse_tag_set_basic() {
         let input = r#""host" = "123", "region" = "us-west""#;
@@ -193,8 +286,8 @@ mod test {
     fn parse_logical_just_equals() {
         let input = r#""host" == "123""#;
 
-        let actual = Logical::parse(input);
-        let expected = Logical::Just(Equality {
+        let actual = Expr::parse(input);
+        let expected = Expr::Eq(Equality {
             op: EqualityOp::Equals,
             lhs: "host".to_string(),
             rhs: "123".to_string(),
@@ -207,8 +300,8 @@ mod test {
     fn parse_logical_just_not_equals() {
         let input = r#""host" != "123""#;
 
-        let actual = Logical::parse(input);
-        let expected = Logical::Just(Equality {
+        let actual = Expr::parse(input);
+        let expected = Expr::Eq(Equality {
             op: EqualityOp::NotEquals,
             lhs: "host".to_string(),
             rhs: "123".to_string(),
@@ -221,15 +314,14 @@ mod test {
     fn parse_logical_and_also() {
         let input = r#""host" == "123" and "region" == "us-west""#;
 
-        let actual = Logical::parse(input);
-        let expected = Logical::Also(
-            Equality {
+        let actual = Expr::parse(input);
+        let expected = Expr::And(
+            Box::new(Expr::Eq(Equality {
                 op: EqualityOp::Equals,
                 lhs: "host".to_string(),
                 rhs: "123".to_string(),
-            },
-            LogicalOp::And,
-            Box::new(Logical::Just(Equality {
+            })),
+            Box::new(Expr::Eq(Equality {
                 op: EqualityOp::Equals,
                 lhs: "region".to_string(),
                 rhs: "us-west".to_string(),
@@ -243,15 +335,14 @@ mod test {
     fn parse_logical_or_also() {
         let input = r#""host" == "123" or "region" == "us-west""#;
 
-        let actual = Logical::parse(input);
-        let expected = Logical::Also(
-            Equality {
+        let actual = Expr::parse(input);
+        let expected = Expr::Or(
+            Box::new(Expr::Eq(Equality {
                 op: EqualityOp::Equals,
                 lhs: "host".to_string(),
                 rhs: "123".to_string(),
-            },
-            LogicalOp::Or,
-            Box::new(Logical::Just(Equality {
+            })),
+            Box::new(Expr::Eq(Equality {
                 op: EqualityOp::Equals,
                 lhs: "region".to_string(),
                 rhs: "us-west".to_string(),
@@ -260,4 +351,60 @@ mod test {
 
         assert_eq!(actual, expected)
     }
+
+    #[test]
+    fn parse_logical_and_binds_tighter_than_or() {
+        let input = r#""a" == "x" or "b" == "y" and "c" == "z""#;
+
+        let actual = Expr::parse(input);
+        let expected = Expr::Or(
+            Box::new(Expr::Eq(Equality {
+                op: EqualityOp::Equals,
+                lhs: "a".to_string(),
+                rhs: "x".to_string(),
+            })),
+            Box::new(Expr::And(
+                Box::new(Expr::Eq(Equality {
+                    op: EqualityOp::Equals,
+                    lhs: "b".to_string(),
+                    rhs: "y".to_string(),
+                })),
+                Box::new(Expr::Eq(Equality {
+                    op: EqualityOp::Equals,
+                    lhs: "c".to_string(),
+                    rhs: "z".to_string(),
+                })),
+            )),
+        );
+
+        assert_eq!(actual, expected)
+    }
+
+    #[test]
+    fn parse_logical_parens_override_precedence() {
+        let input = r#"("a" == "x" or "b" == "y") and "c" == "z""#;
+
+        let actual = Expr::parse(input);
+        let expected = Expr::And(
+            Box::new(Expr::Or(
+                Box::new(Expr::Eq(Equality {
+                    op: EqualityOp::Equals,
+                    lhs: "a".to_string(),
+                    rhs: "x".to_string(),
+                })),
+                Box::new(Expr::Eq(Equality {
+                    op: EqualityOp::Equals,
+                    lhs: "b".to_string(),
+                    rhs: "y".to_string(),
+                })),
+            )),
+            Box::new(Expr::Eq(Equality {
+                op: EqualityOp::Equals,
+                lhs: "c".to_string(),
+                rhs: "z".to_string(),
+            })),
+        );
+
+        assert_eq!(actual, expected)
+    }
 }
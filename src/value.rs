@@ -0,0 +1,194 @@
+use std::io::{self, Read, Write};
+
+const TAG_BOOL: u8 = 0;
+const TAG_U64: u8 = 1;
+const TAG_I64: u8 = 2;
+const TAG_F64: u8 = 3;
+const TAG_TEXT: u8 = 4;
+const TAG_BINARY: u8 = 5;
+
+/// A single, self-describing stored sample. Each variant has a compact
+/// tagged binary encoding -- a one-byte type tag followed by its
+/// little-endian payload, length-prefixed for `Text`/`Binary` -- so the
+/// same representation works in memory and on disk.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    U64(u64),
+    I64(i64),
+    F64(f64),
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl Value {
+    pub(crate) fn type_tag(&self) -> u8 {
+        match self {
+            Value::Bool(_) => TAG_BOOL,
+            Value::U64(_) => TAG_U64,
+            Value::I64(_) => TAG_I64,
+            Value::F64(_) => TAG_F64,
+            Value::Text(_) => TAG_TEXT,
+            Value::Binary(_) => TAG_BINARY,
+        }
+    }
+
+    pub(crate) fn type_name(&self) -> &'static str {
+        Self::type_name_for_tag(self.type_tag())
+    }
+
+    pub(crate) fn type_name_for_tag(tag: u8) -> &'static str {
+        match tag {
+            TAG_BOOL => "bool",
+            TAG_U64 => "u64",
+            TAG_I64 => "i64",
+            TAG_F64 => "f64",
+            TAG_TEXT => "text",
+            TAG_BINARY => "binary",
+            _ => unreachable!("unknown value type tag {}", tag),
+        }
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        writer.write_all(&[self.type_tag()])?;
+
+        match self {
+            Value::Bool(b) => writer.write_all(&[*b as u8]),
+            Value::U64(v) => writer.write_all(&v.to_le_bytes()),
+            Value::I64(v) => writer.write_all(&v.to_le_bytes()),
+            Value::F64(v) => writer.write_all(&v.to_le_bytes()),
+            Value::Text(s) => write_payload(writer, s.as_bytes()),
+            Value::Binary(bytes) => write_payload(writer, bytes),
+        }
+    }
+
+    // Coerces numeric variants to `f64` for aggregation. Panics on
+    // `Text`/`Binary`, which have no numeric interpretation.
+    pub(crate) fn as_f64(&self) -> f64 {
+        match self {
+            Value::Bool(b) => {
+                if *b {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Value::U64(v) => *v as f64,
+            Value::I64(v) => *v as f64,
+            Value::F64(v) => *v,
+            Value::Text(_) | Value::Binary(_) => {
+                panic!("cannot aggregate a {} value numerically", self.type_name())
+            }
+        }
+    }
+
+    pub(crate) fn read_from(reader: &mut impl Read) -> io::Result<Option<Value>> {
+        let mut tag = [0u8; 1];
+
+        match reader.read_exact(&mut tag) {
+            Ok(()) => {}
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+
+        let value = match tag[0] {
+            TAG_BOOL => {
+                let mut buf = [0u8; 1];
+                reader.read_exact(&mut buf)?;
+                Value::Bool(buf[0] != 0)
+            }
+            TAG_U64 => Value::U64(read_u64(reader)?),
+            TAG_I64 => Value::I64(read_i64(reader)?),
+            TAG_F64 => Value::F64(read_f64(reader)?),
+            TAG_TEXT => {
+                let bytes = read_payload(reader)?;
+                Value::Text(String::from_utf8(bytes).expect("text value was not valid utf-8"))
+            }
+            TAG_BINARY => Value::Binary(read_payload(reader)?),
+            tag => unreachable!("unknown value type tag {}", tag),
+        };
+
+        Ok(Some(value))
+    }
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_i64(reader: &mut impl Read) -> io::Result<i64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(i64::from_le_bytes(buf))
+}
+
+fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(f64::from_le_bytes(buf))
+}
+
+fn write_payload(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_payload(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let len = read_u64(reader)? as usize;
+    let mut buf = vec![0u8; len];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn roundtrip(value: Value) -> Value {
+        let mut buf = Vec::new();
+        value.write_to(&mut buf).unwrap();
+
+        Value::read_from(&mut &buf[..]).unwrap().unwrap()
+    }
+
+    #[test]
+    fn encodes_and_decodes_every_variant() {
+        assert_eq!(roundtrip(Value::Bool(true)), Value::Bool(true));
+        assert_eq!(roundtrip(Value::Bool(false)), Value::Bool(false));
+        assert_eq!(roundtrip(Value::U64(42)), Value::U64(42));
+        assert_eq!(roundtrip(Value::I64(-42)), Value::I64(-42));
+        assert_eq!(roundtrip(Value::F64(3.125)), Value::F64(3.125));
+        assert_eq!(
+            roundtrip(Value::Text("hello".to_string())),
+            Value::Text("hello".to_string())
+        );
+        assert_eq!(
+            roundtrip(Value::Binary(vec![1, 2, 3])),
+            Value::Binary(vec![1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn read_from_empty_reader_is_none() {
+        let mut empty: &[u8] = &[];
+
+        assert_eq!(Value::read_from(&mut empty).unwrap(), None);
+    }
+
+    #[test]
+    fn as_f64_coerces_numeric_variants() {
+        assert_eq!(Value::Bool(true).as_f64(), 1.0);
+        assert_eq!(Value::Bool(false).as_f64(), 0.0);
+        assert_eq!(Value::U64(7).as_f64(), 7.0);
+        assert_eq!(Value::I64(-7).as_f64(), -7.0);
+        assert_eq!(Value::F64(2.5).as_f64(), 2.5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn as_f64_panics_on_text() {
+        Value::Text("hello".to_string()).as_f64();
+    }
+}